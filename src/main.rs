@@ -1,48 +1,95 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
 use actix_web::middleware::Logger;
-use actix_web::{get, web, App, HttpResponse, HttpServer};
+use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
+use base64::Engine;
 use chrono::prelude::*;
+use futures::future::{join_all, ready, LocalBoxFuture, Ready};
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use reqwest;
 use serde_derive::Deserialize;
 use sha2::{Digest, Sha256};
+use sodiumoxide::crypto::box_;
+use ssri::Integrity;
 use std::{
+    collections::HashMap,
     fs::read_to_string,
-    sync::{Arc, Mutex},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
     time::Duration,
 };
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::Notify;
 
 const REFRESH_HASH_IN_SECONDS: u64 = 60;
+const IMAGE_CACHE_DIR: &str = "image-cache";
+const UPSTREAM_CONNECT_TIMEOUT_SECONDS: u64 = 10;
+const UPSTREAM_REQUEST_TIMEOUT_SECONDS: u64 = 120;
+const MAX_FETCH_ATTEMPTS: u32 = 3;
 
 #[derive(Debug, Deserialize)]
 struct Config {
-    secrets: Secrets,
+    images: HashMap<String, ImageEntry>,
+    #[serde(default)]
+    auth: AuthConfig,
 }
 
-#[derive(Debug, Deserialize)]
-struct Secrets {
-    en_image: String,
-    en_image_p: String,
-    es_image: String,
-    es_image_p: String,
-    fr_image: String,
-    po_image: String,
-    it_image: String,
-    de_image: String,
+#[derive(Debug, Deserialize, Clone)]
+struct ImageEntry {
+    url: String,
 }
 
-struct AppState {
-    en_image_hash: Mutex<String>,
-    en_p_image_hash: Mutex<String>,
-    es_image_hash: Mutex<String>,
-    es_p_image_hash: Mutex<String>,
-    fr_image_hash: Mutex<String>,
-    po_image_hash: Mutex<String>,
-    it_image_hash: Mutex<String>,
-    de_image_hash: Mutex<String>,
+#[derive(Debug, Deserialize, Default)]
+struct AuthConfig {
+    #[serde(default)]
+    enabled: bool,
+    precomputed_key: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedImage {
+    hash: String,
+    data: Integrity,
+    content_type: String,
+    content_length: u64,
+    last_modified: String,
+}
+
+struct ImageState {
+    url: Mutex<String>,
+    hash: Mutex<String>,
+    cache: Mutex<Option<CachedImage>>,
+    etag: Mutex<Option<String>>,
+    /// Signalled every time `hash` changes, so a `/watch` caller parked on
+    /// `notified()` wakes only for this image rather than any of them.
     notify: Notify,
 }
 
+impl ImageState {
+    fn new(url: String) -> Self {
+        ImageState {
+            url: Mutex::new(url),
+            hash: Mutex::new(String::new()),
+            cache: Mutex::new(None),
+            etag: Mutex::new(None),
+            notify: Notify::new(),
+        }
+    }
+}
+
+struct AppState {
+    images: RwLock<HashMap<String, Arc<ImageState>>>,
+    shutdown: AtomicBool,
+    auth: RwLock<Option<Arc<dyn ApiAuth>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchQuery {
+    last_seen: Option<String>,
+}
+
 impl Config {
     fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let config_str = read_to_string(filename)
@@ -53,100 +100,443 @@ impl Config {
     }
 }
 
-macro_rules! download_and_hash_image {
-    ($state_mu:expr, $image:expr, $state_notify:expr) => {
-        let image_data = match reqwest::get($image).await {
-            Ok(response) => match response.bytes().await {
-                Ok(data) => data,
-                Err(e) => {
-                    let now: DateTime<Utc> = Utc::now();
-                    eprintln!("{} : Error reading response bytes: {}", now, e);
-                    continue;
-                }
-            },
+async fn refresh_image(client: &reqwest::Client, image_state: &Arc<ImageState>) {
+    let url = image_state.url.lock().unwrap().clone();
+
+    let mut attempt = 0;
+    let response = loop {
+        attempt += 1;
+
+        let mut request = client.get(&url);
+        if let Some(etag) = image_state.etag.lock().unwrap().clone() {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = image_state
+            .cache
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|c| c.last_modified.clone())
+        {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+
+        match request.send().await {
+            Ok(response) => break response,
+            Err(e) if attempt < MAX_FETCH_ATTEMPTS => {
+                let now: DateTime<Utc> = Utc::now();
+                eprintln!(
+                    "{} : Error fetching {} (attempt {}/{}): {}, retrying",
+                    now, url, attempt, MAX_FETCH_ATTEMPTS, e
+                );
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+            }
             Err(e) => {
                 let now: DateTime<Utc> = Utc::now();
-                eprintln!("{} : Error fetching image: {}", now, e);
-                continue;
+                eprintln!(
+                    "{} : Error fetching {} after {} attempts: {}",
+                    now, url, attempt, e
+                );
+                return;
             }
-        };
-        let hash = format!("{:x}", Sha256::digest(&image_data));
-        {
-            let mut image_hash = $state_mu.lock().unwrap();
-            *image_hash = hash.clone();
-            $state_notify.notify_one();
         }
     };
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return;
+    }
+
+    let upstream_etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let last_modified = response
+        .headers()
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| Utc::now().to_rfc2822());
+
+    let image_data = match response.bytes().await {
+        Ok(data) => data,
+        Err(e) => {
+            let now: DateTime<Utc> = Utc::now();
+            eprintln!("{} : Error reading response bytes for {}: {}", now, url, e);
+            return;
+        }
+    };
+
+    let hash = format!("{:x}", Sha256::digest(&image_data));
+    let integrity = match cacache::write(IMAGE_CACHE_DIR, &hash, &image_data[..]).await {
+        Ok(integrity) => integrity,
+        Err(e) => {
+            let now: DateTime<Utc> = Utc::now();
+            eprintln!("{} : Error writing {} to cache: {}", now, url, e);
+            return;
+        }
+    };
+
+    *image_state.hash.lock().unwrap() = hash.clone();
+    *image_state.cache.lock().unwrap() = Some(CachedImage {
+        hash,
+        data: integrity,
+        content_type,
+        content_length: image_data.len() as u64,
+        last_modified,
+    });
+    *image_state.etag.lock().unwrap() = upstream_etag;
+    image_state.notify.notify_waiters();
 }
-async fn download_and_hash_images(state: Arc<AppState>, config: Config) {
-    let en_image = config.secrets.en_image.clone();
-    let en_p_image = config.secrets.en_image_p.clone();
-    let es_image = config.secrets.es_image.clone();
-    let es_p_image = config.secrets.es_image_p.clone();
-    let fr_image = config.secrets.fr_image.clone();
-    let po_image = config.secrets.po_image.clone();
-    let it_image = config.secrets.it_image.clone();
-    let de_image = config.secrets.de_image.clone();
+
+async fn download_and_hash_images(state: Arc<AppState>) {
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(UPSTREAM_CONNECT_TIMEOUT_SECONDS))
+        .timeout(Duration::from_secs(UPSTREAM_REQUEST_TIMEOUT_SECONDS))
+        .build()
+        .expect("Failed to build upstream HTTP client");
 
     loop {
-        download_and_hash_image!(state.en_image_hash, &en_image, state.notify);
-        download_and_hash_image!(state.en_p_image_hash, &en_p_image, state.notify);
-        download_and_hash_image!(state.es_image_hash, &es_image, state.notify);
-        download_and_hash_image!(state.es_p_image_hash, &es_p_image, state.notify);
-        download_and_hash_image!(state.fr_image_hash, &fr_image, state.notify);
-        download_and_hash_image!(state.po_image_hash, &po_image, state.notify);
-        download_and_hash_image!(state.it_image_hash, &it_image, state.notify);
-        download_and_hash_image!(state.de_image_hash, &de_image, state.notify);
+        if state.shutdown.load(Ordering::SeqCst) {
+            eprintln!("Refresh loop stopping on shutdown signal");
+            return;
+        }
+
+        // Snapshot the registry so a concurrent SIGHUP reload can mutate the
+        // map without us holding the lock across the `.await`s below.
+        let snapshot: Vec<Arc<ImageState>> = state.images.read().unwrap().values().cloned().collect();
+
+        // Fetch every image concurrently so one slow origin only bounds the
+        // cycle by its own fetch time (up to MAX_FETCH_ATTEMPTS timed-out
+        // attempts plus backoff) rather than by the sum of every image.
+        join_all(snapshot.iter().map(|image_state| refresh_image(&client, image_state))).await;
 
         tokio::time::sleep(Duration::from_secs(REFRESH_HASH_IN_SECONDS)).await;
     }
 }
 
-macro_rules! create_hash_endpoint {
-    ($state_field:ident, $route:expr) => {
-        #[get($route)]
-        async fn $state_field(state: web::Data<Arc<AppState>>) -> HttpResponse {
-            let image_hash = state.$state_field.lock().unwrap();
-            HttpResponse::Ok().body(image_hash.clone())
+fn reload_config(state: &Arc<AppState>) {
+    let config = match Config::load_from_file("Config.toml") {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("SIGHUP: not reloading, error reading config: {}", e);
+            return;
+        }
+    };
+
+    let mut images = state.images.write().unwrap();
+    images.retain(|name, _| config.images.contains_key(name));
+
+    for (name, entry) in config.images {
+        match images.get(&name) {
+            Some(existing) => *existing.url.lock().unwrap() = entry.url,
+            None => {
+                images.insert(name, Arc::new(ImageState::new(entry.url)));
+            }
+        }
+    }
+
+    eprintln!("SIGHUP: reloaded Config.toml, now tracking {} image(s)", images.len());
+
+    match build_auth(&config.auth) {
+        Ok(auth) => *state.auth.write().unwrap() = auth,
+        Err(e) => eprintln!("SIGHUP: keeping previous auth config, error: {}", e),
+    }
+}
+
+fn build_auth(config: &AuthConfig) -> Result<Option<Arc<dyn ApiAuth>>, String> {
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    let key_b64 = config
+        .precomputed_key
+        .clone()
+        .ok_or_else(|| "auth.enabled is true but no auth.precomputed_key was configured".to_string())?;
+    let key_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(key_b64)
+        .map_err(|e| format!("Invalid auth.precomputed_key: {}", e))?;
+    let precomputed_key = box_::PrecomputedKey::from_slice(&key_bytes)
+        .ok_or_else(|| "auth.precomputed_key is not a valid precomputed box key".to_string())?;
+
+    Ok(Some(Arc::new(SealedBoxAuth::new(precomputed_key)) as Arc<dyn ApiAuth>))
+}
+
+trait ApiAuth: Send + Sync {
+    fn validate_token(&self, token: &str) -> bool;
+}
+
+struct SealedBoxAuth {
+    precomputed_key: box_::PrecomputedKey,
+}
+
+impl SealedBoxAuth {
+    fn new(precomputed_key: box_::PrecomputedKey) -> Self {
+        SealedBoxAuth { precomputed_key }
+    }
+}
+
+impl ApiAuth for SealedBoxAuth {
+    fn validate_token(&self, token: &str) -> bool {
+        let decoded = match base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(token) {
+            Ok(decoded) => decoded,
+            Err(_) => return false,
+        };
+
+        if decoded.len() <= box_::NONCEBYTES {
+            return false;
+        }
+
+        let (nonce_bytes, ciphertext) = decoded.split_at(box_::NONCEBYTES);
+        let nonce = match box_::Nonce::from_slice(nonce_bytes) {
+            Some(nonce) => nonce,
+            None => return false,
+        };
+
+        let plaintext = match box_::open_precomputed(ciphertext, &nonce, &self.precomputed_key) {
+            Ok(plaintext) => plaintext,
+            Err(_) => return false,
+        };
+
+        let expiry = match std::str::from_utf8(&plaintext)
+            .ok()
+            .and_then(|s| s.parse::<DateTime<Utc>>().ok())
+        {
+            Some(expiry) => expiry,
+            None => return false,
+        };
+
+        expiry > Utc::now()
+    }
+}
+
+#[derive(Clone)]
+struct AuthMiddlewareFactory {
+    state: Arc<AppState>,
+}
+
+impl AuthMiddlewareFactory {
+    fn new(state: Arc<AppState>) -> Self {
+        AuthMiddlewareFactory { state }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AuthMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<actix_web::body::EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthMiddleware {
+            service: Rc::new(service),
+            state: Arc::clone(&self.state),
+        }))
+    }
+}
+
+struct AuthMiddleware<S> {
+    service: Rc<S>,
+    state: Arc<AppState>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<actix_web::body::EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Read fresh on every request (rather than capturing it once at
+        // startup) so a SIGHUP-driven `reload_config` can rotate or toggle
+        // auth without restarting the server.
+        let auth = self.state.auth.read().unwrap().clone();
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let Some(auth) = auth else {
+                return service.call(req).await.map(ServiceResponse::map_into_left_body);
+            };
+
+            let authorized = req
+                .headers()
+                .get(actix_web::http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(|token| auth.validate_token(token))
+                .unwrap_or(false);
+
+            if authorized {
+                service.call(req).await.map(ServiceResponse::map_into_left_body)
+            } else {
+                let response = HttpResponse::Unauthorized().finish();
+                Ok(req.into_response(response.map_into_right_body()))
+            }
+        })
+    }
+}
+
+async fn hash_endpoint(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+) -> HttpResponse {
+    let image_state = match state.images.read().unwrap().get(path.as_str()) {
+        Some(image_state) => Arc::clone(image_state),
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    let hash = image_state.hash.lock().unwrap().clone();
+
+    if let Some(if_none_match) = req.headers().get("If-None-Match") {
+        if if_none_match.to_str().map(|v| v == hash).unwrap_or(false) {
+            return HttpResponse::NotModified()
+                .insert_header(("ETag", hash))
+                .finish();
+        }
+    }
+
+    HttpResponse::Ok()
+        .insert_header(("ETag", hash.clone()))
+        .body(hash)
+}
+
+async fn image_endpoint(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<Arc<AppState>>,
+) -> HttpResponse {
+    let image_state = match state.images.read().unwrap().get(path.as_str()) {
+        Some(image_state) => Arc::clone(image_state),
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    // Read hash and metadata out of a single `cache` lock so a concurrent
+    // `refresh_image` update can't hand back one field's old value paired
+    // with another field's new one.
+    let cached = match image_state.cache.lock().unwrap().clone() {
+        Some(cached) => cached,
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    if let Some(if_none_match) = req.headers().get("If-None-Match") {
+        if if_none_match
+            .to_str()
+            .map(|v| v == cached.hash)
+            .unwrap_or(false)
+        {
+            return HttpResponse::NotModified()
+                .insert_header(("ETag", cached.hash))
+                .finish();
+        }
+    }
+
+    let data = match cacache::read_hash(IMAGE_CACHE_DIR, &cached.data).await {
+        Ok(data) => data,
+        Err(e) => {
+            let now: DateTime<Utc> = Utc::now();
+            eprintln!("{} : Error reading cached image from disk: {}", now, e);
+            return HttpResponse::InternalServerError().finish();
         }
     };
+
+    HttpResponse::Ok()
+        .content_type(cached.content_type.clone())
+        .insert_header(("Content-Length", cached.content_length.to_string()))
+        .insert_header(("Last-Modified", cached.last_modified.clone()))
+        .insert_header(("Cache-Control", "public, max-age=60"))
+        .insert_header(("ETag", cached.hash.clone()))
+        .body(data)
 }
 
-create_hash_endpoint!(en_image_hash, "/en");
-create_hash_endpoint!(en_p_image_hash, "/en_p");
-create_hash_endpoint!(es_image_hash, "/es");
-create_hash_endpoint!(es_p_image_hash, "/es_p");
-create_hash_endpoint!(fr_image_hash, "/fr");
-create_hash_endpoint!(po_image_hash, "/po");
-create_hash_endpoint!(it_image_hash, "/it");
-create_hash_endpoint!(de_image_hash, "/de");
+async fn watch_endpoint(
+    path: web::Path<String>,
+    query: web::Query<WatchQuery>,
+    state: web::Data<Arc<AppState>>,
+) -> HttpResponse {
+    let image_state = match state.images.read().unwrap().get(path.as_str()) {
+        Some(image_state) => Arc::clone(image_state),
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    let last_seen = query.into_inner().last_seen.unwrap_or_default();
+
+    loop {
+        let notified = image_state.notify.notified();
+
+        let current = image_state.hash.lock().unwrap().clone();
+        if !current.is_empty() && current != last_seen {
+            return HttpResponse::Ok()
+                .insert_header(("ETag", current.clone()))
+                .body(current);
+        }
 
+        notified.await;
+    }
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    sodiumoxide::init().expect("Failed to initialize libsodium");
+
+    let config = Config::load_from_file("Config.toml").unwrap_or_else(|e| {
+        eprintln!("Error loading config: {}", e);
+        std::process::exit(1);
+    });
+
+    let auth = build_auth(&config.auth).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let images = config
+        .images
+        .into_iter()
+        .map(|(name, entry)| (name, Arc::new(ImageState::new(entry.url))))
+        .collect();
+
     let app_state = Arc::new(AppState {
-        en_image_hash: Mutex::new(String::new()),
-        en_p_image_hash: Mutex::new(String::new()),
-        es_image_hash: Mutex::new(String::new()),
-        es_p_image_hash: Mutex::new(String::new()),
-        fr_image_hash: Mutex::new(String::new()),
-        po_image_hash: Mutex::new(String::new()),
-        it_image_hash: Mutex::new(String::new()),
-        de_image_hash: Mutex::new(String::new()),
-        notify: Notify::new(),
+        images: RwLock::new(images),
+        shutdown: AtomicBool::new(false),
+        auth: RwLock::new(auth),
     });
 
     let app_state_clone = Arc::clone(&app_state);
-
     tokio::spawn(async move {
-        let config = Config::load_from_file("Config.toml").unwrap_or_else(|e| {
-            eprintln!("Error loading config: {}", e);
-            std::process::exit(1);
-        });
-
-        download_and_hash_images(app_state_clone, config).await;
+        download_and_hash_images(app_state_clone).await;
     });
 
+    {
+        let app_state = Arc::clone(&app_state);
+        tokio::spawn(async move {
+            let mut hangup =
+                signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler");
+            loop {
+                hangup.recv().await;
+                reload_config(&app_state);
+            }
+        });
+    }
+
     let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
     builder
         .set_private_key_file("certs/key.pem", SslFiletype::PEM)
@@ -155,20 +545,99 @@ async fn main() -> std::io::Result<()> {
         .set_certificate_chain_file("certs/cert.pem")
         .unwrap();
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(app_state.clone()))
-            .wrap(Logger::default())
-            .service(en_image_hash)
-            .service(en_p_image_hash)
-            .service(es_image_hash)
-            .service(es_p_image_hash)
-            .service(fr_image_hash)
-            .service(po_image_hash)
-            .service(it_image_hash)
-            .service(de_image_hash)
+    let server = HttpServer::new({
+        let app_state = app_state.clone();
+        move || {
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .wrap(AuthMiddlewareFactory::new(app_state.clone()))
+                .wrap(Logger::default())
+                .route("/{name}", web::get().to(hash_endpoint))
+                .route("/{name}/image", web::get().to(image_endpoint))
+                .route("/{name}/watch", web::get().to(watch_endpoint))
+        }
     })
     .bind_openssl("0.0.0.0:9191", builder)?
-    .run()
-    .await
+    .run();
+
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        let mut sigint =
+            signal(SignalKind::interrupt()).expect("Failed to register SIGINT handler");
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
+        tokio::select! {
+            _ = sigint.recv() => {},
+            _ = sigterm.recv() => {},
+        }
+        eprintln!("Shutdown signal received, stopping refresh loop and draining connections");
+        app_state.shutdown.store(true, Ordering::SeqCst);
+        server_handle.stop(true).await;
+    });
+
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issuer_and_auth() -> (box_::PrecomputedKey, SealedBoxAuth) {
+        sodiumoxide::init().ok();
+        let (pk_a, sk_a) = box_::gen_keypair();
+        let (pk_b, sk_b) = box_::gen_keypair();
+        let issuer_key = box_::precompute(&pk_b, &sk_a);
+        let verifier_key = box_::precompute(&pk_a, &sk_b);
+        assert_eq!(issuer_key, verifier_key);
+
+        (issuer_key, SealedBoxAuth::new(verifier_key))
+    }
+
+    fn seal_token(key: &box_::PrecomputedKey, expiry: DateTime<Utc>) -> String {
+        let nonce = box_::gen_nonce();
+        let ciphertext = box_::seal_precomputed(expiry.to_rfc3339().as_bytes(), &nonce, key);
+        let mut payload = nonce.as_ref().to_vec();
+        payload.extend_from_slice(&ciphertext);
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(payload)
+    }
+
+    #[test]
+    fn accepts_a_token_with_a_future_expiry() {
+        let (issuer_key, auth) = issuer_and_auth();
+        let token = seal_token(&issuer_key, Utc::now() + chrono::Duration::hours(1));
+
+        assert!(auth.validate_token(&token));
+    }
+
+    #[test]
+    fn rejects_an_expired_token() {
+        let (issuer_key, auth) = issuer_and_auth();
+        let token = seal_token(&issuer_key, Utc::now() - chrono::Duration::hours(1));
+
+        assert!(!auth.validate_token(&token));
+    }
+
+    #[test]
+    fn rejects_a_token_sealed_with_the_wrong_key() {
+        let (_, auth) = issuer_and_auth();
+        let (wrong_key, _) = issuer_and_auth();
+        let token = seal_token(&wrong_key, Utc::now() + chrono::Duration::hours(1));
+
+        assert!(!auth.validate_token(&token));
+    }
+
+    #[test]
+    fn rejects_garbage_base64() {
+        let (_, auth) = issuer_and_auth();
+
+        assert!(!auth.validate_token("not valid base64!!!"));
+    }
+
+    #[test]
+    fn rejects_a_truncated_nonce() {
+        let (_, auth) = issuer_and_auth();
+
+        let short = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode([0u8; 4]);
+        assert!(!auth.validate_token(&short));
+    }
 }